@@ -0,0 +1,440 @@
+//! Non-template report backends.
+//!
+//! `report::generate` renders coverage through Tera templates, which is great
+//! for the human-facing HTML report but useless for feeding a CI service. The
+//! backends in this module bypass Tera entirely and serialize the aggregated
+//! coverage model straight to the exchange formats those services understand
+//! (LCOV tracefiles, Cobertura XML, Coveralls JSON, …).
+//!
+//! Every backend consumes the same [`Coverage`] intermediate representation so
+//! they agree, line for line, with the counts the HTML template shows.
+
+use cov::{Gcda, Gcno, Graph, Interner};
+use error::Result;
+use glob::glob;
+use sourcepath::{identify_source_path, SourceType};
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A branch outcome attached to a line: `block`/`branch` identify the branch in
+/// the GCOV block graph, `taken` is the hit count, or `None` when the branch
+/// was never evaluated (rendered as `-` in LCOV).
+pub struct BranchCov {
+    pub block: usize,
+    pub branch: usize,
+    pub taken: Option<u64>,
+}
+
+/// A single instrumented line and the branches that originate from it.
+pub struct LineCov {
+    pub line: u32,
+    pub hits: u64,
+    pub branches: Vec<BranchCov>,
+}
+
+/// An instrumented function, keyed by the line it starts on.
+pub struct FuncCov {
+    pub start_line: u32,
+    pub name: String,
+    pub hits: u64,
+}
+
+/// Aggregated coverage for one source file.
+pub struct FileCov {
+    pub path: PathBuf,
+    pub lines: Vec<LineCov>,
+    pub functions: Vec<FuncCov>,
+}
+
+/// The whole coverage model, already filtered by the `--include` source types.
+pub struct Coverage {
+    pub files: Vec<FileCov>,
+}
+
+/// Builds the aggregated [`Coverage`] model from the `*.gcno`/`*.gcda` artifacts
+/// below `cov_build_path`, keeping only the files whose source type is allowed.
+///
+/// This mirrors how `report::generate` assembles its model before handing it to
+/// Tera, so the numbers the CI backends emit match the HTML report exactly.
+pub fn collect(cov_build_path: &Path, allowed_source_types: SourceType) -> Result<Coverage> {
+    let mut interner = Interner::new();
+    let mut graph = Graph::new();
+
+    let gcno_pattern = cov_build_path.join("gcno/**/*.gcno");
+    for entry in glob(&gcno_pattern.to_string_lossy())? {
+        let gcno = Gcno::open(entry?, &mut interner)?;
+        graph.merge_gcno(gcno)?;
+    }
+    let gcda_pattern = cov_build_path.join("gcda/**/*.gcda");
+    for entry in glob(&gcda_pattern.to_string_lossy())? {
+        let gcda = Gcda::open(entry?, &mut interner)?;
+        graph.merge_gcda(gcda)?;
+    }
+
+    graph.analyze();
+    let report = graph.report();
+
+    let mut files = Vec::new();
+    for (&symbol, file) in &report.files {
+        let path = PathBuf::from(interner[symbol].to_owned());
+        let (source_type, _) = identify_source_path(&path.to_string_lossy());
+        if !allowed_source_types.contains(source_type) {
+            continue;
+        }
+
+        let lines = file.lines
+            .iter()
+            .map(|(&line, l)| LineCov {
+                line,
+                hits: l.count,
+                branches: l.branches
+                    .iter()
+                    .enumerate()
+                    .map(|(branch, b)| BranchCov {
+                        block: b.block,
+                        branch,
+                        taken: if b.filled { Some(b.count) } else { None },
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        let functions = file.functions
+            .iter()
+            .map(|f| FuncCov {
+                start_line: f.line,
+                name: interner[f.name].to_owned(),
+                hits: f.summary.entry_count,
+            })
+            .collect();
+
+        files.push(FileCov { path, lines, functions });
+    }
+
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(Coverage { files })
+}
+
+/// Writes an [LCOV tracefile] (`lcov.info`) into `cov_build_path` and returns
+/// its path. The tracefile is consumable by `genhtml`, Codecov and Coveralls.
+///
+/// [LCOV tracefile]: https://linux.die.net/man/1/geninfo
+pub fn write_lcov(coverage: &Coverage, cov_build_path: &Path) -> Result<PathBuf> {
+    let output = cov_build_path.join("lcov.info");
+    let mut writer = BufWriter::new(File::create(&output)?);
+
+    for file in &coverage.files {
+        writeln!(writer, "TN:")?;
+        writeln!(writer, "SF:{}", file.path.display())?;
+
+        let mut functions_found = 0;
+        let mut functions_hit = 0;
+        for function in &file.functions {
+            writeln!(writer, "FN:{},{}", function.start_line, function.name)?;
+            functions_found += 1;
+        }
+        for function in &file.functions {
+            writeln!(writer, "FNDA:{},{}", function.hits, function.name)?;
+            if function.hits > 0 {
+                functions_hit += 1;
+            }
+        }
+        writeln!(writer, "FNF:{}", functions_found)?;
+        writeln!(writer, "FNH:{}", functions_hit)?;
+
+        let mut branches_found = 0;
+        let mut branches_hit = 0;
+        for line in &file.lines {
+            for branch in &line.branches {
+                let taken = match branch.taken {
+                    Some(count) => count.to_string(),
+                    None => "-".to_owned(),
+                };
+                writeln!(writer, "BRDA:{},{},{},{}", line.line, branch.block, branch.branch, taken)?;
+                branches_found += 1;
+                if branch.taken.map_or(false, |c| c > 0) {
+                    branches_hit += 1;
+                }
+            }
+        }
+        writeln!(writer, "BRF:{}", branches_found)?;
+        writeln!(writer, "BRH:{}", branches_hit)?;
+
+        let mut lines_found = 0;
+        let mut lines_hit = 0;
+        for line in &file.lines {
+            writeln!(writer, "DA:{},{}", line.line, line.hits)?;
+            lines_found += 1;
+            if line.hits > 0 {
+                lines_hit += 1;
+            }
+        }
+        writeln!(writer, "LF:{}", lines_found)?;
+        writeln!(writer, "LH:{}", lines_hit)?;
+
+        writeln!(writer, "end_of_record")?;
+    }
+
+    writer.flush()?;
+    Ok(output)
+}
+
+/// Overall line-coverage percentage of an already-collected [`Coverage`] model,
+/// in the `0.0 ..= 100.0` range. Returns `100.0` when nothing is instrumented so
+/// an empty build never trips a gate.
+///
+/// Pass `--include local` to restrict the gate to first-party code.
+pub fn line_coverage_percent(coverage: &Coverage) -> f64 {
+    let (mut covered, mut total) = (0u64, 0u64);
+    for file in &coverage.files {
+        for line in &file.lines {
+            total += 1;
+            if line.hits > 0 {
+                covered += 1;
+            }
+        }
+    }
+    if total == 0 { 100.0 } else { covered as f64 / total as f64 * 100.0 }
+}
+
+/// Writes a [Coveralls] job payload (`coveralls.json`) into `cov_build_path` and
+/// returns its path, ready for a later step to POST to `coveralls.io`. Each
+/// source file carries its md5 digest and a line-sized coverage array holding
+/// `null` for non-instrumented lines and the hit count otherwise.
+///
+/// `service_name`/`service_job_id` are pulled from the environment
+/// (`COVERALLS_SERVICE_NAME`/`COVERALLS_SERVICE_JOB_ID`, falling back to the
+/// `TRAVIS_*` names CI providers set) when present.
+///
+/// [Coveralls]: https://docs.coveralls.io/api-introduction
+pub fn write_coveralls(coverage: &Coverage, cov_build_path: &Path) -> Result<PathBuf> {
+    use std::env::var;
+
+    let repo_root = ::std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+
+    let mut source_files = Vec::with_capacity(coverage.files.len());
+    for file in &coverage.files {
+        let contents = match ::std::fs::read(&file.path) {
+            Ok(contents) => contents,
+            Err(_) => continue, // the source moved since the build; skip it.
+        };
+        let newlines = contents.iter().filter(|&&b| b == b'\n').count();
+        // A trailing newline terminates the last line rather than starting a new
+        // one, so only the unterminated-final-line case needs the extra slot. An
+        // empty file has no lines at all.
+        let line_count = if contents.is_empty() {
+            0
+        } else if contents.last() == Some(&b'\n') {
+            newlines
+        } else {
+            newlines + 1
+        };
+
+        let mut line_coverage = vec![::serde_json::Value::Null; line_count];
+        for line in &file.lines {
+            if let Some(slot) = line_coverage.get_mut((line.line as usize).saturating_sub(1)) {
+                *slot = json!(line.hits);
+            }
+        }
+
+        let name = file.path.strip_prefix(&repo_root).unwrap_or(&file.path).to_string_lossy().into_owned();
+        source_files.push(json!({
+            "name": name,
+            "source_digest": format!("{:x}", md5::compute(&contents)),
+            "coverage": line_coverage,
+        }));
+    }
+
+    let service_name = var("COVERALLS_SERVICE_NAME")
+        .or_else(|_| var("TRAVIS").map(|_| "travis-ci".to_owned()))
+        .unwrap_or_else(|_| "cargo-cov".to_owned());
+    let mut payload = json!({
+        "service_name": service_name,
+        "source_files": source_files,
+    });
+    if let Ok(job_id) = var("COVERALLS_SERVICE_JOB_ID").or_else(|_| var("TRAVIS_JOB_ID")) {
+        payload["service_job_id"] = json!(job_id);
+    }
+    if let Ok(commit) = var("GIT_COMMIT").or_else(|_| var("TRAVIS_COMMIT")) {
+        let branch = var("GIT_BRANCH").or_else(|_| var("TRAVIS_BRANCH")).unwrap_or_default();
+        payload["git"] = json!({
+            "head": { "id": commit },
+            "branch": branch,
+        });
+    }
+
+    let output = cov_build_path.join("coveralls.json");
+    let mut writer = BufWriter::new(File::create(&output)?);
+    ::serde_json::to_writer(&mut writer, &payload)?;
+    writer.flush()?;
+    Ok(output)
+}
+
+/// XML-escapes a string for use in an attribute value.
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Covered/total as a ratio, returning `0.0` when nothing is instrumented so we
+/// never emit a `NaN` rate into the XML.
+fn rate(covered: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        covered as f64 / total as f64
+    }
+}
+
+/// Writes a [Cobertura] `cobertura.xml` report into `cov_build_path` and returns
+/// its path. Source files are grouped into packages by their directory relative
+/// to the workspace root so Jenkins' Coverage plugin and GitLab render a sensible
+/// package tree.
+///
+/// [Cobertura]: https://cobertura.github.io/cobertura/
+pub fn write_cobertura(coverage: &Coverage, cov_build_path: &Path) -> Result<PathBuf> {
+    let workspace_root = ::std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+
+    // Group files into packages keyed by their directory relative to the root.
+    let mut packages: BTreeMap<String, Vec<&FileCov>> = BTreeMap::new();
+    for file in &coverage.files {
+        let relative = file.path.strip_prefix(&workspace_root).unwrap_or(&file.path);
+        let package = relative
+            .parent()
+            .map_or_else(String::new, |p| p.to_string_lossy().replace('/', "."));
+        packages.entry(package).or_insert_with(Vec::new).push(file);
+    }
+
+    let (mut lines_covered, mut lines_valid) = (0u64, 0u64);
+    let (mut branches_covered, mut branches_valid) = (0u64, 0u64);
+    for file in &coverage.files {
+        for line in &file.lines {
+            lines_valid += 1;
+            if line.hits > 0 {
+                lines_covered += 1;
+            }
+            for branch in &line.branches {
+                branches_valid += 1;
+                if branch.taken.map_or(false, |c| c > 0) {
+                    branches_covered += 1;
+                }
+            }
+        }
+    }
+
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+
+    let output = cov_build_path.join("cobertura.xml");
+    let mut writer = BufWriter::new(File::create(&output)?);
+
+    writeln!(writer, "<?xml version=\"1.0\" ?>")?;
+    writeln!(writer, "<!DOCTYPE coverage SYSTEM \"http://cobertura.sourceforge.net/xml/coverage-04.dtd\">")?;
+    writeln!(
+        writer,
+        "<coverage line-rate=\"{:.4}\" branch-rate=\"{:.4}\" lines-covered=\"{}\" lines-valid=\"{}\" branches-covered=\"{}\" branches-valid=\"{}\" complexity=\"0\" version=\"cargo-cov\" timestamp=\"{}\">",
+        rate(lines_covered, lines_valid),
+        rate(branches_covered, branches_valid),
+        lines_covered,
+        lines_valid,
+        branches_covered,
+        branches_valid,
+        timestamp,
+    )?;
+    writeln!(writer, "  <sources>\n    <source>{}</source>\n  </sources>", escape_xml(&workspace_root.to_string_lossy()))?;
+    writeln!(writer, "  <packages>")?;
+
+    for (name, files) in &packages {
+        let (mut pkg_lc, mut pkg_lv) = (0u64, 0u64);
+        let (mut pkg_bc, mut pkg_bv) = (0u64, 0u64);
+        for file in files {
+            for line in &file.lines {
+                pkg_lv += 1;
+                if line.hits > 0 {
+                    pkg_lc += 1;
+                }
+                for branch in &line.branches {
+                    pkg_bv += 1;
+                    if branch.taken.map_or(false, |c| c > 0) {
+                        pkg_bc += 1;
+                    }
+                }
+            }
+        }
+
+        writeln!(
+            writer,
+            "    <package name=\"{}\" line-rate=\"{:.4}\" branch-rate=\"{:.4}\" complexity=\"0\">",
+            escape_xml(name),
+            rate(pkg_lc, pkg_lv),
+            rate(pkg_bc, pkg_bv),
+        )?;
+        writeln!(writer, "      <classes>")?;
+
+        for file in files {
+            let relative = file.path.strip_prefix(&workspace_root).unwrap_or(&file.path);
+            let filename = relative.to_string_lossy();
+            let class_name = relative.file_stem().map_or_else(|| filename.to_string(), |s| s.to_string_lossy().into_owned());
+
+            let (mut cls_lc, mut cls_lv) = (0u64, 0u64);
+            let (mut cls_bc, mut cls_bv) = (0u64, 0u64);
+            for line in &file.lines {
+                cls_lv += 1;
+                if line.hits > 0 {
+                    cls_lc += 1;
+                }
+                for branch in &line.branches {
+                    cls_bv += 1;
+                    if branch.taken.map_or(false, |c| c > 0) {
+                        cls_bc += 1;
+                    }
+                }
+            }
+
+            writeln!(
+                writer,
+                "        <class name=\"{}\" filename=\"{}\" line-rate=\"{:.4}\" branch-rate=\"{:.4}\" complexity=\"0\">",
+                escape_xml(&class_name),
+                escape_xml(&filename),
+                rate(cls_lc, cls_lv),
+                rate(cls_bc, cls_bv),
+            )?;
+            writeln!(writer, "          <methods/>")?;
+            writeln!(writer, "          <lines>")?;
+            for line in &file.lines {
+                if line.branches.is_empty() {
+                    writeln!(writer, "            <line number=\"{}\" hits=\"{}\"/>", line.line, line.hits)?;
+                } else {
+                    let taken = line.branches.iter().filter(|b| b.taken.map_or(false, |c| c > 0)).count();
+                    let total = line.branches.len();
+                    writeln!(
+                        writer,
+                        "            <line number=\"{}\" hits=\"{}\" branch=\"true\" condition-coverage=\"{}% ({}/{})\"/>",
+                        line.line,
+                        line.hits,
+                        (rate(taken as u64, total as u64) * 100.0).round() as u64,
+                        taken,
+                        total,
+                    )?;
+                }
+            }
+            writeln!(writer, "          </lines>")?;
+            writeln!(writer, "        </class>")?;
+        }
+
+        writeln!(writer, "      </classes>")?;
+        writeln!(writer, "    </package>")?;
+    }
+
+    writeln!(writer, "  </packages>")?;
+    writeln!(writer, "</coverage>")?;
+
+    writer.flush()?;
+    Ok(output)
+}
@@ -63,6 +63,7 @@ mod lookup;
 mod argparse;
 mod cargo;
 mod report;
+mod export;
 mod template;
 mod sourcepath;
 
@@ -72,12 +73,13 @@ use clap::ArgMatches;
 use error::{Error, Result};
 use sourcepath::*;
 
-use std::ffi::OsStr;
+use std::ffi::{OsStr, OsString};
 use std::io::{self, Write};
 
 fn main() {
     if let Err(error) = run() {
-        print_error(error).expect("error while printing error 🤷")
+        print_error(error).expect("error while printing error 🤷");
+        ::std::process::exit(1);
     }
 }
 
@@ -117,10 +119,34 @@ fn run() -> Result<()> {
     let matches = matches.unwrap();
     update_from_clap(matches, &mut special_args);
 
-    let forward_args = match matches.values_of_os("args") {
+    let mut forward_args = match matches.values_of_os("args") {
         Some(args) => normalize(args, &mut special_args),
         None => Vec::new(),
     };
+
+    // Forward workspace package selection straight through to cargo. `cargo run`
+    // only understands `-p`/`--package`, so `--workspace`/`--exclude` are scoped
+    // to `build`/`test` to avoid an "unexpected argument" error. The lcov/
+    // cobertura/coveralls backends then span every selected member because
+    // `export::collect` globs the shared cov build directory recursively.
+    if let Some(specs) = matches.values_of_os("package") {
+        for spec in specs {
+            forward_args.push(OsString::from("--package"));
+            forward_args.push(spec.to_owned());
+        }
+    }
+    if let "build" | "test" = subcommand {
+        if matches.is_present("workspace") {
+            forward_args.push(OsString::from("--workspace"));
+        }
+        if let Some(specs) = matches.values_of_os("exclude") {
+            for spec in specs {
+                forward_args.push(OsString::from("--exclude"));
+                forward_args.push(spec.to_owned());
+            }
+        }
+    }
+
     let cargo = Cargo::new(special_args, forward_args)?;
 
     match subcommand {
@@ -153,6 +179,9 @@ fn parse_args() -> clap::ArgMatches<'static> {
             (@arg profiler: --profiler [LIB] +global "Path to `libclang_rt.profile_*.a`")
             (@arg target: --target [TRIPLE] +global "Target triple which the covered program will run in")
             (@arg ("manifest-path"): --("manifest-path") [PATH] +global "Path to the manifest of the package")
+            (@arg package: --package -p [SPEC]... +global +use_delimiter "Package(s) to operate on (workspace members)")
+            (@arg workspace: --workspace +global "Operate on all members of the workspace")
+            (@arg exclude: --exclude [SPEC]... +global +use_delimiter "Exclude the given workspace members")
             (@subcommand build =>
                 (about: "Compile the crate and produce coverage data (*.gcno)")
                 (@setting TrailingValues) // FIXME: TrailingValues is undocumented and may be wrong.
@@ -176,6 +205,7 @@ fn parse_args() -> clap::ArgMatches<'static> {
             (@subcommand report =>
                 (about: "Generates a coverage report")
                 (@arg template: --template [TEMPLATE] "Report template, default to 'html'")
+                (@arg ("fail-under"): --("fail-under") [PERCENT] "Exit with an error if line coverage is below this percentage")
                 (@arg open: --open "Open the report in browser after it is generated")
                 (@arg include: --include [TYPES]... +use_delimiter possible_values(&[
                     "local",
@@ -195,7 +225,36 @@ fn generate_reports(cargo: &Cargo, matches: &ArgMatches) -> Result<()> {
     let allowed_source_types = matches.values_of("include").map_or(SOURCE_TYPE_DEFAULT, |it| SourceType::from_multi_str(it).unwrap());
 
     let template = matches.value_of_os("template").unwrap_or_else(|| OsStr::new("html"));
-    let open_path = report::generate(cargo.cov_build_path(), template, allowed_source_types)?;
+
+    // The non-template backends and the `--fail-under` gate share a single
+    // aggregated model, so for the lcov/cobertura/coveralls path the
+    // `*.gcno`/`*.gcda` are globbed and parsed once. The default html path keeps
+    // building its own model inside `report::generate`, so `--fail-under` with
+    // html parses the artifacts twice — once here for the gate, once for Tera.
+    let is_export = match template.to_str() {
+        Some("lcov") | Some("cobertura") | Some("coveralls") => true,
+        _ => false,
+    };
+    let model = if is_export || matches.is_present("fail-under") {
+        Some(export::collect(cargo.cov_build_path(), allowed_source_types)?)
+    } else {
+        None
+    };
+
+    let open_path = match template.to_str() {
+        Some("lcov") => Some(export::write_lcov(model.as_ref().unwrap(), cargo.cov_build_path())?),
+        Some("cobertura") => Some(export::write_cobertura(model.as_ref().unwrap(), cargo.cov_build_path())?),
+        Some("coveralls") => Some(export::write_coveralls(model.as_ref().unwrap(), cargo.cov_build_path())?),
+        _ => report::generate(cargo.cov_build_path(), template, allowed_source_types)?,
+    };
+
+    if let Some(threshold) = matches.value_of("fail-under") {
+        let threshold = threshold.parse::<f64>().map_err(|_| Error::from("invalid --fail-under value"))?;
+        let coverage = export::line_coverage_percent(model.as_ref().expect("fail-under collects the model"));
+        if coverage < threshold {
+            bail!("line coverage {:.2}% is below the --fail-under threshold of {:.2}%", coverage, threshold);
+        }
+    }
 
     if matches.is_present("open") {
         if let Some(path) = open_path {